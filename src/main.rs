@@ -6,23 +6,443 @@ use axum::{
     Router,
 };
 use chrono::{DateTime, Utc};
-use image::{self, imageops::FilterType, ImageDecoder, ImageReader, DynamicImage};
+use exif::{In, Tag};
+use image::{self, imageops::FilterType, ImageDecoder, ImageEncoder, ImageReader, DynamicImage};
 use serde::Deserialize;
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::env;
 use std::fs;
-use std::path::Path as FilePath;
-use std::time::SystemTime;
+use std::io::BufReader;
+use std::path::{Path as FilePath, PathBuf};
+use std::time::{Duration, SystemTime};
 use tower_http::services::ServeDir;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use urlencoding;
 
+/// Directory holding cached, already-resized/encoded images. Configurable via `CACHE_DIR`.
+fn cache_dir() -> PathBuf {
+    PathBuf::from(env::var("CACHE_DIR").unwrap_or_else(|_| "cache".to_string()))
+}
+
+/// Total size budget for the cache directory, in bytes. Configurable via `CACHE_MAX_BYTES`.
+fn cache_max_bytes() -> u64 {
+    env::var("CACHE_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1_073_741_824) // 1 GiB
+}
+
+/// Everything about a resize request that affects the bytes `cache_key` is keyed on, bundled so
+/// the function doesn't grow a positional parameter per knob.
+struct ResizeKeyParams {
+    width: Option<u32>,
+    height: Option<u32>,
+    fit: FitMode,
+    format: image::ImageFormat,
+    quality: Option<u8>,
+    watermark: Option<WatermarkPosition>,
+}
+
+fn cache_key(full_path: &FilePath, params: &ResizeKeyParams, source_modified: SystemTime) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(full_path.to_string_lossy().as_bytes());
+    hasher.update(params.width.unwrap_or(0).to_le_bytes());
+    hasher.update(params.height.unwrap_or(0).to_le_bytes());
+    hasher.update(params.fit.as_str().as_bytes());
+    hasher.update(format!("{:?}", params.format).as_bytes());
+    hasher.update([params.quality.unwrap_or(0)]);
+    hasher.update(params.watermark.map(WatermarkPosition::as_str).unwrap_or("none").as_bytes());
+    let since_epoch = source_modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    hasher.update(since_epoch.as_nanos().to_le_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Reads a cached, already-encoded image if present, touching its mtime for LRU tracking.
+fn read_from_cache(key: &str) -> Option<Vec<u8>> {
+    let path = cache_dir().join(key);
+    let buffer = fs::read(&path).ok()?;
+    if let Ok(file) = fs::File::open(&path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+    Some(buffer)
+}
+
+/// Writes `buffer` into the cache under `key`, atomically via a temp file + rename.
+fn write_to_cache(key: &str, buffer: &[u8]) {
+    let dir = cache_dir();
+    if let Err(e) = fs::create_dir_all(&dir) {
+        warn!("Failed to create cache dir {:?}: {}", dir, e);
+        return;
+    }
+    // Per-call unique suffix: `process::id()` alone collides across concurrent misses on the
+    // same key within this process, letting two writers race on one temp path.
+    static NEXT_TMP_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let call_id = NEXT_TMP_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let tmp_path = dir.join(format!("{}.tmp-{}-{}", key, std::process::id(), call_id));
+    if let Err(e) = fs::write(&tmp_path, buffer) {
+        warn!("Failed to write cache temp file {:?}: {}", tmp_path, e);
+        return;
+    }
+    if let Err(e) = fs::rename(&tmp_path, dir.join(key)) {
+        warn!("Failed to finalize cache entry {:?}: {}", key, e);
+        let _ = fs::remove_file(&tmp_path);
+    }
+}
+
+/// Evicts least-recently-accessed cache entries until the directory is back under budget.
+fn cleanup_cache_once(max_bytes: u64) {
+    evict_lru_until_under_budget(&cache_dir(), max_bytes);
+}
+
+/// Core of the cache cleanup, taking the directory explicitly so it can be unit-tested against a
+/// scratch directory instead of the real, env-configured cache.
+fn evict_lru_until_under_budget(dir: &FilePath, max_bytes: u64) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut files: Vec<(PathBuf, u64, SystemTime)> = Vec::new();
+    let mut total: u64 = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if let Ok(metadata) = entry.metadata() {
+            if !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            total += metadata.len();
+            files.push((path, metadata.len(), modified));
+        }
+    }
+
+    if total <= max_bytes {
+        return;
+    }
+
+    files.sort_by_key(|(_, _, modified)| *modified);
+    for (path, size, _) in files {
+        if total <= max_bytes {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total = total.saturating_sub(size);
+        }
+    }
+}
+
+/// Spawns the background task that periodically enforces `CACHE_MAX_BYTES`.
+fn spawn_cache_cleanup_task() {
+    let max_bytes = cache_max_bytes();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            cleanup_cache_once(max_bytes);
+        }
+    });
+}
+
 #[derive(Deserialize)]
 struct ResizeParams {
     width: Option<u32>,
     height: Option<u32>,
+    /// Deprecated in favor of `fit`; still honored when `fit` is absent (`true` => `contain`,
+    /// `false` => `fill`).
     preserve_aspect_ratio: Option<bool>,
+    fit: Option<String>,
+    format: Option<String>,
+    quality: Option<u8>,
+    watermark: Option<bool>,
+    watermark_position: Option<String>,
+}
+
+/// Which corner of the image a watermark is anchored to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl WatermarkPosition {
+    fn as_str(self) -> &'static str {
+        match self {
+            WatermarkPosition::TopLeft => "top-left",
+            WatermarkPosition::TopRight => "top-right",
+            WatermarkPosition::BottomLeft => "bottom-left",
+            WatermarkPosition::BottomRight => "bottom-right",
+        }
+    }
+}
+
+/// Resolves the requested watermark corner: an explicit `watermark_position` query param wins,
+/// otherwise the server-wide `WATERMARK_POSITION` env var, defaulting to `bottom-right`.
+fn resolve_watermark_position(requested: Option<&str>) -> WatermarkPosition {
+    let from_str = |value: &str| match value.to_lowercase().as_str() {
+        "top-left" | "topleft" => Some(WatermarkPosition::TopLeft),
+        "top-right" | "topright" => Some(WatermarkPosition::TopRight),
+        "bottom-left" | "bottomleft" => Some(WatermarkPosition::BottomLeft),
+        "bottom-right" | "bottomright" => Some(WatermarkPosition::BottomRight),
+        other => {
+            warn!("Ignoring unrecognized watermark position: {}", other);
+            None
+        }
+    };
+
+    if let Some(requested) = requested {
+        if let Some(position) = from_str(requested) {
+            return position;
+        }
+    }
+    env::var("WATERMARK_POSITION")
+        .ok()
+        .and_then(|v| from_str(&v))
+        .unwrap_or(WatermarkPosition::BottomRight)
+}
+
+/// Computes the top-left pixel offset to overlay a `wm_width`x`wm_height` watermark onto an
+/// `img_width`x`img_height` image in the given corner, inset by `margin` pixels.
+fn watermark_offset(
+    img_width: u32,
+    img_height: u32,
+    wm_width: u32,
+    wm_height: u32,
+    margin: i64,
+    position: WatermarkPosition,
+) -> (i64, i64) {
+    let margin = margin.max(0);
+    let (x, y) = match position {
+        WatermarkPosition::TopLeft => (margin, margin),
+        WatermarkPosition::TopRight => (img_width as i64 - wm_width as i64 - margin, margin),
+        WatermarkPosition::BottomLeft => (margin, img_height as i64 - wm_height as i64 - margin),
+        WatermarkPosition::BottomRight => (
+            img_width as i64 - wm_width as i64 - margin,
+            img_height as i64 - wm_height as i64 - margin,
+        ),
+    };
+    (x.max(0), y.max(0))
+}
+
+/// Loads the configured watermark image once, from `WATERMARK_PATH`, caching the result.
+/// Returns `None` if the env var is unset or the image fails to load.
+fn watermark_image() -> Option<&'static DynamicImage> {
+    static WATERMARK: std::sync::OnceLock<Option<DynamicImage>> = std::sync::OnceLock::new();
+    WATERMARK
+        .get_or_init(|| {
+            let path = env::var("WATERMARK_PATH").ok()?;
+            match image::open(&path) {
+                Ok(img) => Some(img),
+                Err(e) => {
+                    error!("Failed to load watermark image {:?}: {}", path, e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// Resolves whether a request should be watermarked: an explicit `watermark` query param wins,
+/// otherwise falling back to the server-wide `WATERMARK_ALWAYS_ON` policy.
+fn resolve_watermark(requested: Option<bool>) -> bool {
+    requested.unwrap_or_else(|| {
+        env::var("WATERMARK_ALWAYS_ON")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false)
+    })
+}
+
+/// Composites the configured watermark onto `img`'s `position` corner, scaled to ~20% of its
+/// shorter edge. A no-op if no watermark is configured.
+fn apply_watermark(mut img: DynamicImage, position: WatermarkPosition) -> DynamicImage {
+    let Some(watermark) = watermark_image() else {
+        return img;
+    };
+
+    let shorter_edge = img.width().min(img.height());
+    let target_width = ((shorter_edge as f64 * 0.2).round() as u32).max(1);
+    let scale = target_width as f64 / watermark.width().max(1) as f64;
+    let target_height = ((watermark.height() as f64 * scale).round() as u32).max(1);
+    let scaled_watermark = watermark.resize(target_width, target_height, FilterType::Lanczos3);
+
+    let margin = (shorter_edge as f64 * 0.02).round() as i64;
+    let (x, y) = watermark_offset(
+        img.width(),
+        img.height(),
+        scaled_watermark.width(),
+        scaled_watermark.height(),
+        margin,
+        position,
+    );
+    image::imageops::overlay(&mut img, &scaled_watermark, x, y);
+    img
+}
+
+/// How a resized image fills its requested `width`x`height` box.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum FitMode {
+    /// Scale to fit inside the box, preserving aspect ratio (the old `resize`).
+    Contain,
+    /// Stretch to exactly fill the box, ignoring aspect ratio (the old `resize_exact`).
+    Fill,
+    /// Scale to fill the box, preserving aspect ratio, then center-crop the overflow.
+    Cover,
+    /// Center-crop to the box without scaling.
+    Crop,
+}
+
+impl FitMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            FitMode::Contain => "contain",
+            FitMode::Fill => "fill",
+            FitMode::Cover => "cover",
+            FitMode::Crop => "crop",
+        }
+    }
+}
+
+/// Resolves the requested `fit` mode, falling back to the deprecated `preserve_aspect_ratio`
+/// boolean when `fit` is absent.
+fn resolve_fit_mode(fit: Option<&str>, preserve_aspect_ratio: Option<bool>) -> FitMode {
+    if let Some(fit) = fit {
+        match fit.to_lowercase().as_str() {
+            "contain" => return FitMode::Contain,
+            "fill" | "stretch" => return FitMode::Fill,
+            "cover" => return FitMode::Cover,
+            "crop" => return FitMode::Crop,
+            other => warn!("Ignoring unrecognized fit query parameter: {}", other),
+        }
+    }
+    if preserve_aspect_ratio.unwrap_or(false) {
+        FitMode::Contain
+    } else {
+        FitMode::Fill
+    }
+}
+
+/// Resizes/crops `img` into a `width`x`height` box according to `mode`.
+fn apply_fit(img: DynamicImage, width: u32, height: u32, mode: FitMode) -> DynamicImage {
+    match mode {
+        FitMode::Contain => img.resize(width, height, FilterType::Lanczos3),
+        FitMode::Fill => img.resize_exact(width, height, FilterType::Lanczos3),
+        FitMode::Cover => {
+            let scale = (width as f64 / img.width() as f64).max(height as f64 / img.height() as f64);
+            let scaled_width = (img.width() as f64 * scale).round() as u32;
+            let scaled_height = (img.height() as f64 * scale).round() as u32;
+            let scaled = img.resize_exact(scaled_width.max(1), scaled_height.max(1), FilterType::Lanczos3);
+            let x = (scaled.width().saturating_sub(width)) / 2;
+            let y = (scaled.height().saturating_sub(height)) / 2;
+            scaled.crop_imm(x, y, width.min(scaled.width()), height.min(scaled.height()))
+        }
+        FitMode::Crop => {
+            let crop_width = width.min(img.width());
+            let crop_height = height.min(img.height());
+            let x = (img.width().saturating_sub(crop_width)) / 2;
+            let y = (img.height().saturating_sub(crop_height)) / 2;
+            img.crop_imm(x, y, crop_width, crop_height)
+        }
+    }
+}
+
+/// Maps a `format` query value (`png`, `jpeg`/`jpg`, `webp`, `avif`, `gif`) to its `ImageFormat`.
+fn parse_target_format(format: &str) -> Option<image::ImageFormat> {
+    match format.to_lowercase().as_str() {
+        "png" => Some(image::ImageFormat::Png),
+        "jpeg" | "jpg" => Some(image::ImageFormat::Jpeg),
+        "webp" => Some(image::ImageFormat::WebP),
+        "avif" => Some(image::ImageFormat::Avif),
+        "gif" => Some(image::ImageFormat::Gif),
+        _ => None,
+    }
+}
+
+/// Picks the modern format a client's `Accept` header advertises, if any, preferring AVIF.
+fn negotiate_format_from_accept(accept: &str) -> Option<image::ImageFormat> {
+    if accept.contains("image/avif") {
+        Some(image::ImageFormat::Avif)
+    } else if accept.contains("image/webp") {
+        Some(image::ImageFormat::WebP)
+    } else {
+        None
+    }
+}
+
+/// Resolves the format to encode the response in: an explicit `format` param wins, otherwise
+/// content negotiation against `Accept` is attempted, falling back to the source format.
+fn resolve_target_format(
+    format_param: Option<&str>,
+    accept_header: Option<&str>,
+    source_format: image::ImageFormat,
+) -> image::ImageFormat {
+    if let Some(format_param) = format_param {
+        if let Some(parsed) = parse_target_format(format_param) {
+            return parsed;
+        }
+        warn!("Ignoring unrecognized format query parameter: {}", format_param);
+    }
+    accept_header
+        .and_then(negotiate_format_from_accept)
+        .unwrap_or(source_format)
+}
+
+/// Content-type for a given target `ImageFormat`.
+fn content_type_for_format(format: image::ImageFormat) -> &'static str {
+    match format {
+        image::ImageFormat::Png => "image/png",
+        image::ImageFormat::Jpeg => "image/jpeg",
+        image::ImageFormat::Gif => "image/gif",
+        image::ImageFormat::WebP => "image/webp",
+        image::ImageFormat::Avif => "image/avif",
+        image::ImageFormat::Tiff => "image/tiff",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Encodes `img` as `format`, honoring `quality` for the lossy formats that support it.
+fn encode_image(
+    img: &DynamicImage,
+    format: image::ImageFormat,
+    quality: Option<u8>,
+) -> image::ImageResult<Vec<u8>> {
+    let mut buffer = Vec::new();
+    match format {
+        image::ImageFormat::Jpeg => {
+            let quality = quality.unwrap_or(80);
+            let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality);
+            encoder.encode_image(img)?;
+        }
+        image::ImageFormat::Avif => {
+            let quality = quality.unwrap_or(80);
+            let encoder =
+                image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut buffer, 4, quality);
+            encoder.write_image(
+                img.as_bytes(),
+                img.width(),
+                img.height(),
+                img.color().into(),
+            )?;
+        }
+        image::ImageFormat::WebP => {
+            // image's WebPEncoder only supports lossless encoding; there's no lossy/quality path
+            // to wire up, so be honest about it instead of silently ignoring the parameter.
+            if quality.is_some() {
+                warn!("Ignoring quality parameter: lossy WebP encoding is not supported, falling back to lossless");
+            }
+            let mut cursor = std::io::Cursor::new(&mut buffer);
+            img.write_to(&mut cursor, format)?;
+        }
+        _ => {
+            let mut cursor = std::io::Cursor::new(&mut buffer);
+            img.write_to(&mut cursor, format)?;
+        }
+    }
+    Ok(buffer)
 }
 
 fn get_entry_type(path: &FilePath) -> &str {
@@ -39,24 +459,125 @@ fn get_entry_type(path: &FilePath) -> &str {
     }
 }
 
-async fn list_handler(Path(path): Path<String>) -> Result<Json<serde_json::Value>, StatusCode> {
+/// Resolves a `{*path}` segment to a path inside `IMAGE_DIR`, applying the usual containment and
+/// dot-prefix checks shared by `list_handler` and `meta_handler`.
+fn resolve_listed_path(path: &str) -> Result<PathBuf, StatusCode> {
     let base_dir = env::var("IMAGE_DIR").unwrap_or_else(|_| "images".to_string());
-    // Construct the full path
     let full_path = if path.is_empty() {
         FilePath::new(&base_dir).to_path_buf()
     } else {
-        let decoded_path = urlencoding::decode(&path).unwrap_or_else(|_| path.clone().into());
+        let decoded_path = urlencoding::decode(path).unwrap_or_else(|_| path.to_string().into());
         FilePath::new(&base_dir).join(&*decoded_path)
     };
-    
-    info!("Attempting to list path: {:?}", full_path);
 
-    // Ensure the path starts with the base directory
     if !full_path.starts_with(&base_dir) || path.starts_with(".") {
         error!("Forbidden path: {:?}", full_path);
         return Err(StatusCode::FORBIDDEN);
     }
 
+    Ok(full_path)
+}
+
+/// Reads the EXIF block of an image file, if any, returning capture metadata as a JSON object.
+/// Returns `None` when the file has no readable EXIF data, so callers can omit the key entirely.
+fn extract_exif(full_path: &FilePath) -> Option<serde_json::Value> {
+    let file = fs::File::open(full_path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    let mut fields = serde_json::Map::new();
+    let mut insert = |tag: Tag, key: &str| {
+        if let Some(field) = exif.get_field(tag, In::PRIMARY) {
+            fields.insert(key.to_string(), json!(field.display_value().to_string()));
+        }
+    };
+
+    insert(Tag::Make, "make");
+    insert(Tag::Model, "model");
+    insert(Tag::LensModel, "lens");
+    insert(Tag::ExposureTime, "exposure_time");
+    insert(Tag::FNumber, "f_number");
+    insert(Tag::PhotographicSensitivity, "iso");
+    insert(Tag::FocalLength, "focal_length");
+    insert(Tag::DateTimeOriginal, "date_time_original");
+
+    if let (Some(lat), Some(lat_ref), Some(lon), Some(lon_ref)) = (
+        exif.get_field(Tag::GPSLatitude, In::PRIMARY),
+        exif.get_field(Tag::GPSLatitudeRef, In::PRIMARY),
+        exif.get_field(Tag::GPSLongitude, In::PRIMARY),
+        exif.get_field(Tag::GPSLongitudeRef, In::PRIMARY),
+    ) {
+        fields.insert(
+            "gps".to_string(),
+            json!({
+                "latitude": lat.display_value().to_string(),
+                "latitude_ref": lat_ref.display_value().to_string(),
+                "longitude": lon.display_value().to_string(),
+                "longitude_ref": lon_ref.display_value().to_string(),
+            }),
+        );
+    }
+
+    if fields.is_empty() {
+        None
+    } else {
+        Some(serde_json::Value::Object(fields))
+    }
+}
+
+/// Builds the JSON description of a single file, including EXIF metadata for images.
+fn describe_file(full_path: &FilePath, download_path: &str, metadata: &fs::Metadata) -> serde_json::Value {
+    let modified_time: DateTime<Utc> = metadata.modified().unwrap_or(SystemTime::now()).into();
+
+    let (width, height) = match image::image_dimensions(full_path) {
+        Ok((w, h)) => (w, h),
+        Err(e) => {
+            error!("Failed to read image dimensions for {:?}: {}", full_path, e);
+            (0, 0)
+        }
+    };
+
+    let download_url = format!("/download/{}", download_path);
+
+    let mut entry = json!({
+        "name": full_path.file_name().unwrap().to_str().unwrap(),
+        "size": metadata.len(),
+        "modified": modified_time.to_rfc3339(),
+        "width": width,
+        "height": height,
+        "download_url": download_url,
+        "type": get_entry_type(full_path),
+    });
+
+    if let Some(exif) = extract_exif(full_path) {
+        entry["exif"] = exif;
+    }
+
+    entry
+}
+
+async fn meta_handler(Path(path): Path<String>) -> Result<Json<serde_json::Value>, StatusCode> {
+    let full_path = resolve_listed_path(&path)?;
+    info!("Attempting to read metadata for path: {:?}", full_path);
+
+    let metadata = match fs::metadata(&full_path) {
+        Ok(m) => m,
+        Err(_) => return Err(StatusCode::NOT_FOUND),
+    };
+
+    if metadata.is_dir() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(Json(describe_file(&full_path, &path, &metadata)))
+}
+
+async fn list_handler(Path(path): Path<String>) -> Result<Json<serde_json::Value>, StatusCode> {
+    let full_path = resolve_listed_path(&path)?;
+
+    info!("Attempting to list path: {:?}", full_path);
+
     let metadata = match fs::metadata(&full_path) {
         Ok(m) => m,
         Err(_) => return Err(StatusCode::NOT_FOUND),
@@ -89,31 +610,75 @@ async fn list_handler(Path(path): Path<String>) -> Result<Json<serde_json::Value
         }
         Ok(Json(json!(entries)))
     } else {
-        let modified_time: DateTime<Utc> = metadata.modified().unwrap_or(SystemTime::now()).into();
-        
-        let (width, height) = match image::image_dimensions(&full_path) {
-            Ok((w, h)) => (w, h),
-            Err(e) => {
-                error!("Failed to read image dimensions for {:?}: {}", full_path, e);
-                (0, 0)
-            }
-        };
+        Ok(Json(describe_file(&full_path, &path, &metadata)))
+    }
+}
+
+/// Returns `true` if the request's validators (`If-None-Match` / `If-Modified-Since`) show the
+/// client's cached copy is still fresh, meaning a `304 Not Modified` can be returned.
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified_time: &DateTime<Utc>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        return if_none_match
+            .split(',')
+            .any(|candidate| candidate.trim() == etag || candidate.trim() == "*");
+    }
+    if let Some(if_modified_since) = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+    {
+        if let Ok(since) = DateTime::parse_from_rfc2822(if_modified_since) {
+            return modified_time.timestamp() <= since.timestamp();
+        }
+    }
+    false
+}
+
+/// Parses a single-range `Range: bytes=start-end` header against a body of `total_len` bytes.
+/// Returns `Ok(None)` when there is no range header, `Ok(Some((start, end)))` (inclusive) for a
+/// satisfiable range, or `Err(())` for a range that cannot be satisfied (`416`).
+fn parse_range(range_header: Option<&str>, total_len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(range_header) = range_header else {
+        return Ok(None);
+    };
+    let Some(spec) = range_header.strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    // Only a single range is supported; in a multi-range request only the first range is served.
+    let spec = spec.split(',').next().unwrap_or("").trim();
+    let (start_str, end_str) = spec.split_once('-').ok_or(())?;
 
-        let download_url = format!("/download/{}", path);
+    let (start, end) = if start_str.is_empty() {
+        // Suffix range: "-N" means the last N bytes. "-0" requests zero bytes and is
+        // unsatisfiable, unlike a suffix larger than the file (which just clamps to it all).
+        let suffix_len: u64 = end_str.parse().map_err(|_| ())?;
+        if suffix_len == 0 {
+            return Err(());
+        } else if suffix_len > total_len {
+            (0, total_len.saturating_sub(1))
+        } else {
+            (total_len - suffix_len, total_len - 1)
+        }
+    } else {
+        let start: u64 = start_str.parse().map_err(|_| ())?;
+        let end: u64 = if end_str.is_empty() {
+            total_len.saturating_sub(1)
+        } else {
+            end_str.parse().map_err(|_| ())?
+        };
+        (start, end)
+    };
 
-        Ok(Json(json!({
-            "name": full_path.file_name().unwrap().to_str().unwrap(),
-            "size": metadata.len(),
-            "modified": modified_time.to_rfc3339(),
-            "width": width,
-            "height": height,
-            "download_url": download_url,
-            "type": get_entry_type(&full_path),
-        })))
+    if total_len == 0 || start > end || start >= total_len {
+        return Err(());
     }
+    Ok(Some((start, end.min(total_len - 1))))
 }
 
-async fn download_handler(Path(path): Path<String>, params: Query<ResizeParams>) -> Result<Response, StatusCode> {
+async fn download_handler(
+    Path(path): Path<String>,
+    params: Query<ResizeParams>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
     if path.split('/').any(|segment| segment.starts_with(".")) {
         error!("Forbidden path: {:?}", path);
         return Err(StatusCode::FORBIDDEN);
@@ -154,79 +719,215 @@ async fn download_handler(Path(path): Path<String>, params: Query<ResizeParams>)
         }
     };
 
-    let format = reader.format().unwrap_or(image::ImageFormat::Png);
+    let source_format = reader.format().unwrap_or(image::ImageFormat::Png);
+    let accept_header = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+    let format = resolve_target_format(params.format.as_deref(), accept_header, source_format);
+    let fit = resolve_fit_mode(params.fit.as_deref(), params.preserve_aspect_ratio);
+    let watermark = resolve_watermark(params.watermark)
+        .then(|| resolve_watermark_position(params.watermark_position.as_deref()));
 
-    let mut decoder = match reader.into_decoder() {
-        Ok(img) => img,
-        Err(e) => {
-            error!("Failed to decode image: {:?}, error: {}", full_path, e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+    let key_params = ResizeKeyParams {
+        width: params.width,
+        height: params.height,
+        fit,
+        format,
+        quality: params.quality,
+        watermark,
     };
-    let orientation = match decoder.orientation() {
-        Ok(img) => img,
-        Err(e) => {
-            error!("Failed to decode image orientation of image: {:?}, error: {}", full_path, e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    let key = cache_key(&full_path, &key_params, metadata.modified().unwrap_or(SystemTime::now()));
+
+    let content_type = content_type_for_format(format);
+
+    let etag = format!("\"{}\"", key);
+    if is_not_modified(&headers, &etag, &modified_time) {
+        return Ok(build_not_modified_response(content_type, &modified_time, &etag));
+    }
+
+    let range_header = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+
+    if let Some(buffer) = read_from_cache(&key) {
+        info!("Serving cached resize for {:?}", full_path);
+        return build_download_response(content_type, &modified_time, &etag, buffer, range_header.as_deref());
+    }
+
+    let _permit = match tokio::time::timeout(download_queue_timeout(), download_semaphore().acquire()).await {
+        Ok(Ok(permit)) => permit,
+        Ok(Err(_)) => unreachable!("download semaphore is never closed"),
+        Err(_) => {
+            warn!("Timed out waiting for a free decode/encode slot: {:?}", full_path);
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
         }
     };
-    let mut img = match DynamicImage::from_decoder(decoder) {
-        Ok(img) => img,
+
+    let blocking_path = full_path.clone();
+    let width = params.width;
+    let height = params.height;
+    let quality = params.quality;
+
+    let buffer = match tokio::task::spawn_blocking(move || {
+        decode_resize_encode(&blocking_path, format, width, height, fit, quality, watermark)
+    })
+    .await
+    {
+        Ok(result) => result?,
         Err(e) => {
-            error!("Failed to decode image: {:?}, error: {}", full_path, e);
+            error!("Decode/resize/encode task panicked for {:?}: {}", full_path, e);
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
+
+    write_to_cache(&key, &buffer);
+
+    info!("Successfully resized image: {:?}", full_path);
+    build_download_response(content_type, &modified_time, &etag, buffer, range_header.as_deref())
+}
+
+/// Parses `DOWNLOAD_CONCURRENCY`'s value, falling back to `available_parallelism` (or 4) when
+/// unset or invalid. Split out from `download_semaphore` so the fallback logic is unit-testable
+/// without touching the process environment.
+fn parse_concurrency_limit(value: Option<&str>) -> usize {
+    value
+        .and_then(|v| v.parse().ok())
+        .filter(|permits| *permits > 0)
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4))
+}
+
+/// Number of concurrent decode/resize/encode jobs allowed at once. Configurable via
+/// `DOWNLOAD_CONCURRENCY`, defaulting to the number of available CPUs.
+fn download_semaphore() -> &'static tokio::sync::Semaphore {
+    static SEMAPHORE: std::sync::OnceLock<tokio::sync::Semaphore> = std::sync::OnceLock::new();
+    SEMAPHORE.get_or_init(|| {
+        let permits = parse_concurrency_limit(env::var("DOWNLOAD_CONCURRENCY").ok().as_deref());
+        tokio::sync::Semaphore::new(permits)
+    })
+}
+
+/// Parses `DOWNLOAD_QUEUE_TIMEOUT_SECS`'s value, defaulting to 10 seconds when unset or invalid.
+fn parse_queue_timeout_secs(value: Option<&str>) -> u64 {
+    value.and_then(|v| v.parse().ok()).unwrap_or(10)
+}
+
+/// How long a request waits for a free decode/encode slot before failing with `503`.
+/// Configurable via `DOWNLOAD_QUEUE_TIMEOUT_SECS`.
+fn download_queue_timeout() -> Duration {
+    Duration::from_secs(parse_queue_timeout_secs(
+        env::var("DOWNLOAD_QUEUE_TIMEOUT_SECS").ok().as_deref(),
+    ))
+}
+
+/// Decodes, resizes and re-encodes the source image. Runs on a blocking thread pool since
+/// decoding, Lanczos3 resampling, and encoding are all CPU-bound and would otherwise stall the
+/// async runtime.
+fn decode_resize_encode(
+    full_path: &FilePath,
+    format: image::ImageFormat,
+    width: Option<u32>,
+    height: Option<u32>,
+    fit: FitMode,
+    quality: Option<u8>,
+    watermark: Option<WatermarkPosition>,
+) -> Result<Vec<u8>, StatusCode> {
+    let reader = ImageReader::open(full_path).map_err(|e| {
+        error!("Failed to open image file: {:?}, error: {}", full_path, e);
+        StatusCode::NOT_FOUND
+    })?;
+    let reader = reader.with_guessed_format().map_err(|e| {
+        error!("Failed to guess image format: {:?}, error: {}", full_path, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut decoder = reader.into_decoder().map_err(|e| {
+        error!("Failed to decode image: {:?}, error: {}", full_path, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let orientation = decoder.orientation().map_err(|e| {
+        error!("Failed to decode image orientation of image: {:?}, error: {}", full_path, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| {
+        error!("Failed to decode image: {:?}, error: {}", full_path, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
     img.apply_orientation(orientation);
 
-    let processed_img = if let (Some(width), Some(height)) = (params.width, params.height) {
-        if params.preserve_aspect_ratio.unwrap_or(false) {
-            img.resize(width, height, FilterType::Lanczos3)
-        } else {
-            img.resize_exact(width, height, FilterType::Lanczos3)
-        }
+    let processed_img = if let (Some(width), Some(height)) = (width, height) {
+        apply_fit(img, width, height, fit)
     } else {
         img
     };
 
-    let mut buffer = Vec::new();
-    let mut cursor = std::io::Cursor::new(&mut buffer);
+    let processed_img = match watermark {
+        Some(position) => apply_watermark(processed_img, position),
+        None => processed_img,
+    };
 
-    if let Err(e) = processed_img.write_to(&mut cursor, format) {
+    encode_image(&processed_img, format, quality).map_err(|e| {
         error!("Failed to encode image: {:?}, error: {}", full_path, e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
 
-    let content_type = match format {
-        image::ImageFormat::Png => "image/png",
-        image::ImageFormat::Jpeg => "image/jpeg",
-        image::ImageFormat::Gif => "image/gif",
-        image::ImageFormat::WebP => "image/webp",
-        image::ImageFormat::Avif => "image/avif",
-        image::ImageFormat::Tiff => "image/tiff",
-        _ => "application/octet-stream",
-    };
+/// Builds a bodyless `304 Not Modified` response carrying the same headers as a full response.
+fn build_not_modified_response(
+    content_type: &'static str,
+    modified_time: &DateTime<Utc>,
+    etag: &str,
+) -> Response {
+    let mut headers = common_headers(content_type, modified_time, etag);
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    (StatusCode::NOT_MODIFIED, headers).into_response()
+}
 
-    let headers = {
-        let mut headers = HeaderMap::new();
-        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
-        headers.insert(
-            header::LAST_MODIFIED,
-            HeaderValue::from_str(&modified_time.to_rfc2822()).unwrap(),
-        );
-        headers.insert(
-            header::CACHE_CONTROL,
-            HeaderValue::from_static("public, max-age=31536000"),
-        );
-        headers
-    };
+/// Builds the standard success response for a (possibly cached) resized image, honoring a
+/// `Range` header with `206 Partial Content` or rejecting unsatisfiable ranges with `416`.
+fn build_download_response(
+    content_type: &'static str,
+    modified_time: &DateTime<Utc>,
+    etag: &str,
+    buffer: Vec<u8>,
+    range_header: Option<&str>,
+) -> Result<Response, StatusCode> {
+    let total_len = buffer.len() as u64;
+    let mut headers = common_headers(content_type, modified_time, etag);
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
 
-    info!("Successfully resized image: {:?}", full_path);
-    Ok((
-        headers,
-        buffer,
-    )
-        .into_response())
+    match parse_range(range_header, total_len) {
+        Ok(Some((start, end))) => {
+            let slice = buffer[start as usize..=end as usize].to_vec();
+            headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, total_len)).unwrap(),
+            );
+            Ok((StatusCode::PARTIAL_CONTENT, headers, slice).into_response())
+        }
+        Ok(None) => Ok((headers, buffer).into_response()),
+        Err(()) => {
+            headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{}", total_len)).unwrap(),
+            );
+            Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response())
+        }
+    }
+}
+
+/// Headers shared by every successful, not-modified, and partial-content response.
+fn common_headers(content_type: &'static str, modified_time: &DateTime<Utc>, etag: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(&modified_time.to_rfc2822()).unwrap(),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=31536000"),
+    );
+    headers.insert(header::ETAG, HeaderValue::from_str(etag).unwrap());
+    headers
 }
 
 #[tokio::main]
@@ -238,10 +939,13 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    spawn_cache_cleanup_task();
+
     let app = Router::new()
         .route("/list/{*path}", get(list_handler))
         .route("/list/", get(|| list_handler(Path("".to_string()))))
         .route("/download/{*path}", get(download_handler))
+        .route("/meta/{*path}", get(meta_handler))
         .fallback_service(ServeDir::new("public"));
 
     let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
@@ -250,3 +954,436 @@ async fn main() {
     info!("Listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app).await.unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_absent_header_is_none() {
+        assert_eq!(parse_range(None, 100), Ok(None));
+    }
+
+    #[test]
+    fn parse_range_non_bytes_unit_is_ignored() {
+        assert_eq!(parse_range(Some("items=0-10"), 100), Ok(None));
+    }
+
+    #[test]
+    fn parse_range_simple_bounded() {
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), Ok(Some((0, 9))));
+    }
+
+    #[test]
+    fn parse_range_open_ended_goes_to_last_byte() {
+        assert_eq!(parse_range(Some("bytes=90-"), 100), Ok(Some((90, 99))));
+    }
+
+    #[test]
+    fn parse_range_suffix_returns_last_n_bytes() {
+        assert_eq!(parse_range(Some("bytes=-10"), 100), Ok(Some((90, 99))));
+    }
+
+    #[test]
+    fn parse_range_suffix_larger_than_file_clamps_to_whole_file() {
+        assert_eq!(parse_range(Some("bytes=-1000"), 100), Ok(Some((0, 99))));
+    }
+
+    #[test]
+    fn parse_range_zero_length_suffix_is_unsatisfiable() {
+        assert_eq!(parse_range(Some("bytes=-0"), 100), Err(()));
+    }
+
+    #[test]
+    fn parse_range_start_beyond_end_of_file_is_unsatisfiable() {
+        assert_eq!(parse_range(Some("bytes=100-200"), 100), Err(()));
+    }
+
+    #[test]
+    fn parse_range_start_after_end_is_unsatisfiable() {
+        assert_eq!(parse_range(Some("bytes=50-10"), 100), Err(()));
+    }
+
+    #[test]
+    fn parse_range_against_empty_body_is_unsatisfiable() {
+        assert_eq!(parse_range(Some("bytes=0-0"), 0), Err(()));
+    }
+
+    #[test]
+    fn parse_range_end_past_total_len_clamps() {
+        assert_eq!(parse_range(Some("bytes=50-1000"), 100), Ok(Some((50, 99))));
+    }
+
+    #[test]
+    fn parse_range_multi_range_uses_first_only() {
+        assert_eq!(parse_range(Some("bytes=0-9,20-29"), 100), Ok(Some((0, 9))));
+    }
+
+    #[test]
+    fn is_not_modified_matching_etag() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"abc\""));
+        let modified: DateTime<Utc> = SystemTime::now().into();
+        assert!(is_not_modified(&headers, "\"abc\"", &modified));
+    }
+
+    #[test]
+    fn is_not_modified_wildcard_etag_always_matches() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+        let modified: DateTime<Utc> = SystemTime::now().into();
+        assert!(is_not_modified(&headers, "\"anything\"", &modified));
+    }
+
+    #[test]
+    fn is_not_modified_non_matching_etag() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        let modified: DateTime<Utc> = SystemTime::now().into();
+        assert!(!is_not_modified(&headers, "\"abc\"", &modified));
+    }
+
+    #[test]
+    fn is_not_modified_if_modified_since_at_or_after_mtime() {
+        let mut headers = HeaderMap::new();
+        let modified: DateTime<Utc> = DateTime::parse_from_rfc2822("Tue, 15 Nov 1994 08:12:31 GMT")
+            .unwrap()
+            .into();
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_str(&modified.to_rfc2822()).unwrap(),
+        );
+        assert!(is_not_modified(&headers, "\"etag\"", &modified));
+    }
+
+    #[test]
+    fn is_not_modified_if_modified_since_before_mtime() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Tue, 15 Nov 1994 08:12:31 GMT"),
+        );
+        let modified: DateTime<Utc> = DateTime::parse_from_rfc2822("Wed, 16 Nov 1994 08:12:31 GMT")
+            .unwrap()
+            .into();
+        assert!(!is_not_modified(&headers, "\"etag\"", &modified));
+    }
+
+    #[test]
+    fn is_not_modified_no_validators_is_false() {
+        let headers = HeaderMap::new();
+        let modified: DateTime<Utc> = SystemTime::now().into();
+        assert!(!is_not_modified(&headers, "\"etag\"", &modified));
+    }
+
+    fn sample_key_params() -> ResizeKeyParams {
+        ResizeKeyParams {
+            width: Some(200),
+            height: Some(100),
+            fit: FitMode::Cover,
+            format: image::ImageFormat::Jpeg,
+            quality: Some(80),
+            watermark: None,
+        }
+    }
+
+    #[test]
+    fn cache_key_stable_for_identical_inputs() {
+        let path = FilePath::new("images/a.jpg");
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let a = cache_key(path, &sample_key_params(), mtime);
+        let b = cache_key(path, &sample_key_params(), mtime);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn cache_key_changes_with_quality() {
+        let path = FilePath::new("images/a.jpg");
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut other = sample_key_params();
+        other.quality = Some(40);
+        assert_ne!(
+            cache_key(path, &sample_key_params(), mtime),
+            cache_key(path, &other, mtime)
+        );
+    }
+
+    #[test]
+    fn cache_key_changes_with_fit_mode() {
+        let path = FilePath::new("images/a.jpg");
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut other = sample_key_params();
+        other.fit = FitMode::Crop;
+        assert_ne!(
+            cache_key(path, &sample_key_params(), mtime),
+            cache_key(path, &other, mtime)
+        );
+    }
+
+    #[test]
+    fn cache_key_changes_with_source_mtime() {
+        let path = FilePath::new("images/a.jpg");
+        let a = cache_key(
+            path,
+            &sample_key_params(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+        let b = cache_key(
+            path,
+            &sample_key_params(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_001),
+        );
+        assert_ne!(a, b);
+    }
+
+    /// Scratch cache directory under the OS temp dir, unique per test run.
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+            let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!("imscale-test-{}-{}", name, id));
+            fs::create_dir_all(&dir).unwrap();
+            ScratchDir(dir)
+        }
+
+        fn write_file(&self, name: &str, size: usize, modified: SystemTime) {
+            let path = self.0.join(name);
+            fs::write(&path, vec![0u8; size]).unwrap();
+            fs::File::open(&path).unwrap().set_modified(modified).unwrap();
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn evict_lru_until_under_budget_removes_oldest_first() {
+        let dir = ScratchDir::new("lru-oldest-first");
+        let base = SystemTime::now();
+        dir.write_file("oldest", 10, base - Duration::from_secs(300));
+        dir.write_file("middle", 10, base - Duration::from_secs(200));
+        dir.write_file("newest", 10, base - Duration::from_secs(100));
+
+        evict_lru_until_under_budget(&dir.0, 20);
+
+        assert!(!dir.0.join("oldest").exists());
+        assert!(dir.0.join("middle").exists());
+        assert!(dir.0.join("newest").exists());
+    }
+
+    #[test]
+    fn evict_lru_until_under_budget_noop_when_under_budget() {
+        let dir = ScratchDir::new("lru-under-budget");
+        dir.write_file("only", 10, SystemTime::now());
+
+        evict_lru_until_under_budget(&dir.0, 1_000);
+
+        assert!(dir.0.join("only").exists());
+    }
+
+    #[test]
+    fn evict_lru_until_under_budget_missing_dir_is_noop() {
+        let missing = std::env::temp_dir().join("imscale-test-does-not-exist");
+        evict_lru_until_under_budget(&missing, 0);
+    }
+
+    #[test]
+    fn parse_target_format_recognizes_aliases() {
+        assert_eq!(parse_target_format("jpg"), Some(image::ImageFormat::Jpeg));
+        assert_eq!(parse_target_format("JPEG"), Some(image::ImageFormat::Jpeg));
+        assert_eq!(parse_target_format("png"), Some(image::ImageFormat::Png));
+        assert_eq!(parse_target_format("webp"), Some(image::ImageFormat::WebP));
+        assert_eq!(parse_target_format("avif"), Some(image::ImageFormat::Avif));
+        assert_eq!(parse_target_format("gif"), Some(image::ImageFormat::Gif));
+        assert_eq!(parse_target_format("bmp"), None);
+    }
+
+    #[test]
+    fn negotiate_format_from_accept_prefers_avif_over_webp() {
+        assert_eq!(
+            negotiate_format_from_accept("image/avif,image/webp,*/*"),
+            Some(image::ImageFormat::Avif)
+        );
+    }
+
+    #[test]
+    fn negotiate_format_from_accept_falls_back_to_webp() {
+        assert_eq!(
+            negotiate_format_from_accept("text/html,image/webp"),
+            Some(image::ImageFormat::WebP)
+        );
+    }
+
+    #[test]
+    fn negotiate_format_from_accept_none_for_unsupported() {
+        assert_eq!(negotiate_format_from_accept("text/html,*/*"), None);
+    }
+
+    #[test]
+    fn resolve_target_format_explicit_param_wins() {
+        let resolved = resolve_target_format(Some("png"), Some("image/avif"), image::ImageFormat::Jpeg);
+        assert_eq!(resolved, image::ImageFormat::Png);
+    }
+
+    #[test]
+    fn resolve_target_format_falls_back_to_negotiation() {
+        let resolved = resolve_target_format(None, Some("image/avif"), image::ImageFormat::Jpeg);
+        assert_eq!(resolved, image::ImageFormat::Avif);
+    }
+
+    #[test]
+    fn resolve_target_format_falls_back_to_source_format() {
+        let resolved = resolve_target_format(None, None, image::ImageFormat::Jpeg);
+        assert_eq!(resolved, image::ImageFormat::Jpeg);
+    }
+
+    #[test]
+    fn resolve_target_format_ignores_unrecognized_param() {
+        let resolved = resolve_target_format(Some("bmp"), Some("image/webp"), image::ImageFormat::Png);
+        assert_eq!(resolved, image::ImageFormat::WebP);
+    }
+
+    #[test]
+    fn resolve_fit_mode_from_explicit_param() {
+        assert_eq!(resolve_fit_mode(Some("contain"), None), FitMode::Contain);
+        assert_eq!(resolve_fit_mode(Some("fill"), None), FitMode::Fill);
+        assert_eq!(resolve_fit_mode(Some("stretch"), None), FitMode::Fill);
+        assert_eq!(resolve_fit_mode(Some("cover"), None), FitMode::Cover);
+        assert_eq!(resolve_fit_mode(Some("crop"), None), FitMode::Crop);
+    }
+
+    #[test]
+    fn resolve_fit_mode_falls_back_to_preserve_aspect_ratio() {
+        assert_eq!(resolve_fit_mode(None, Some(true)), FitMode::Contain);
+        assert_eq!(resolve_fit_mode(None, Some(false)), FitMode::Fill);
+        assert_eq!(resolve_fit_mode(None, None), FitMode::Fill);
+    }
+
+    #[test]
+    fn resolve_fit_mode_ignores_unrecognized_param() {
+        assert_eq!(resolve_fit_mode(Some("bogus"), Some(true)), FitMode::Contain);
+    }
+
+    fn test_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::new_rgb8(width, height)
+    }
+
+    #[test]
+    fn apply_fit_contain_preserves_aspect_ratio_within_box() {
+        let result = apply_fit(test_image(200, 100), 50, 50, FitMode::Contain);
+        assert!(result.width() <= 50 && result.height() <= 50);
+        assert_eq!(result.width(), 50);
+        assert_eq!(result.height(), 25);
+    }
+
+    #[test]
+    fn apply_fit_fill_stretches_to_exact_box() {
+        let result = apply_fit(test_image(200, 100), 40, 40, FitMode::Fill);
+        assert_eq!((result.width(), result.height()), (40, 40));
+    }
+
+    #[test]
+    fn apply_fit_cover_fills_box_exactly() {
+        let result = apply_fit(test_image(200, 100), 40, 40, FitMode::Cover);
+        assert_eq!((result.width(), result.height()), (40, 40));
+    }
+
+    #[test]
+    fn apply_fit_crop_never_upscales() {
+        let result = apply_fit(test_image(20, 10), 40, 40, FitMode::Crop);
+        assert_eq!((result.width(), result.height()), (20, 10));
+    }
+
+    #[test]
+    fn apply_fit_crop_centers_within_larger_image() {
+        let result = apply_fit(test_image(100, 100), 20, 20, FitMode::Crop);
+        assert_eq!((result.width(), result.height()), (20, 20));
+    }
+
+    #[test]
+    fn resolve_listed_path_joins_under_default_base_dir() {
+        let resolved = resolve_listed_path("foo/bar.jpg").unwrap();
+        assert_eq!(resolved, FilePath::new("images").join("foo/bar.jpg"));
+    }
+
+    #[test]
+    fn resolve_listed_path_empty_returns_base_dir() {
+        let resolved = resolve_listed_path("").unwrap();
+        assert_eq!(resolved, FilePath::new("images"));
+    }
+
+    #[test]
+    fn resolve_listed_path_rejects_dot_prefixed_paths() {
+        assert_eq!(resolve_listed_path("../secrets"), Err(StatusCode::FORBIDDEN));
+        assert_eq!(resolve_listed_path(".hidden"), Err(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn resolve_listed_path_decodes_url_escapes() {
+        let resolved = resolve_listed_path("a%20b.jpg").unwrap();
+        assert_eq!(resolved, FilePath::new("images").join("a b.jpg"));
+    }
+
+    #[test]
+    fn parse_concurrency_limit_uses_valid_value() {
+        assert_eq!(parse_concurrency_limit(Some("8")), 8);
+    }
+
+    #[test]
+    fn parse_concurrency_limit_falls_back_on_zero_or_invalid() {
+        let fallback = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        assert_eq!(parse_concurrency_limit(Some("0")), fallback);
+        assert_eq!(parse_concurrency_limit(Some("not-a-number")), fallback);
+        assert_eq!(parse_concurrency_limit(None), fallback);
+    }
+
+    #[test]
+    fn parse_queue_timeout_secs_uses_valid_value() {
+        assert_eq!(parse_queue_timeout_secs(Some("30")), 30);
+    }
+
+    #[test]
+    fn parse_queue_timeout_secs_falls_back_to_default() {
+        assert_eq!(parse_queue_timeout_secs(Some("not-a-number")), 10);
+        assert_eq!(parse_queue_timeout_secs(None), 10);
+    }
+
+    #[test]
+    fn resolve_watermark_respects_explicit_param() {
+        assert!(resolve_watermark(Some(true)));
+        assert!(!resolve_watermark(Some(false)));
+    }
+
+    #[test]
+    fn resolve_watermark_position_recognizes_aliases() {
+        assert_eq!(resolve_watermark_position(Some("top-left")), WatermarkPosition::TopLeft);
+        assert_eq!(resolve_watermark_position(Some("topright")), WatermarkPosition::TopRight);
+        assert_eq!(resolve_watermark_position(Some("bottom-left")), WatermarkPosition::BottomLeft);
+        assert_eq!(resolve_watermark_position(Some("bottomright")), WatermarkPosition::BottomRight);
+    }
+
+    #[test]
+    fn resolve_watermark_position_unrecognized_falls_back_to_default() {
+        assert_eq!(resolve_watermark_position(Some("middle")), WatermarkPosition::BottomRight);
+        assert_eq!(resolve_watermark_position(None), WatermarkPosition::BottomRight);
+    }
+
+    #[test]
+    fn watermark_offset_for_each_corner() {
+        assert_eq!(watermark_offset(100, 100, 20, 10, 5, WatermarkPosition::TopLeft), (5, 5));
+        assert_eq!(watermark_offset(100, 100, 20, 10, 5, WatermarkPosition::TopRight), (75, 5));
+        assert_eq!(watermark_offset(100, 100, 20, 10, 5, WatermarkPosition::BottomLeft), (5, 85));
+        assert_eq!(watermark_offset(100, 100, 20, 10, 5, WatermarkPosition::BottomRight), (75, 85));
+    }
+
+    #[test]
+    fn watermark_offset_clamps_negative_margin_and_overflow() {
+        assert_eq!(watermark_offset(100, 100, 20, 10, -5, WatermarkPosition::TopLeft), (0, 0));
+        assert_eq!(watermark_offset(10, 10, 20, 20, 0, WatermarkPosition::BottomRight), (0, 0));
+    }
+}